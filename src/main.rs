@@ -1,12 +1,46 @@
+// Most of the table's API is only exercised by the tests, not by the demo in `main`.
+#![cfg_attr(not(test), allow(dead_code))]
+
 use std::collections::hash_map::DefaultHasher;
+use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
-#[derive(Debug)]
+/// Largest size `rebuild_under_max_probe` will grow a table to.
+const MAX_REBUILD_SIZE: usize = 1 << 20;
+/// Largest size `min_collision_free_size` will try.
+const COLLISION_FREE_SEARCH_LIMIT: usize = 1 << 16;
+/// Largest entry count read from a serialized length that a deserialized table is pre-sized for.
+#[cfg(feature = "serde")]
+const MAX_DESERIALIZE_SIZE_HINT: usize = 4096;
+/// Fraction of slots that can be filled before a table resizes, unless it was built with another.
+const DEFAULT_LOAD_FACTOR: f64 = 0.5;
+/// How many times bigger a table gets each time it resizes.
+const GROWTH_FACTOR: usize = 2;
+/// Probe distance standard deviation above which `balance` rehashes.
+const BALANCE_STDDEV_THRESHOLD: f64 = 1.0;
+
+#[derive(Clone, Debug)]
 struct HashTable<K: Eq + Clone + Hash + Default, V: Clone + Default> {
     size: usize,
     table: Vec<HashTableEntry<K, V>>,
     current_size: usize,
+    /// Keys recorded as known to be missing from the backing source, created on first use.
+    negative: Option<Box<HashTable<K, ()>>>,
+    /// Total slots inspected by `find_slot`, and the number of calls it took to inspect them.
+    probe_count: Counter,
+    lookup_count: Counter,
+    probe_strategy: ProbeStrategy,
+    load_factor: f64,
+    /// When false the table keeps its size, and puts fail once there is no free slot left.
+    auto_resize: bool,
+    /// Bumped on every change to the entries: puts, deletes and in-place value updates.
+    generation: u64,
 }
 
 #[derive(Clone, Debug)]
@@ -15,6 +49,89 @@ struct HashTableEntry<K: Eq + Clone + Hash + Default, V: Clone + Default> {
     value: V,
     is_alive: bool,
     has_been_used: bool,
+    /// Table generation at which this entry was last changed.
+    generation: u64,
+}
+
+/// Statistics counter that `find_slot` can bump through `&self` without making the table `!Sync`.
+#[derive(Debug, Default)]
+struct Counter(AtomicUsize);
+
+impl Counter {
+    fn add(&self, amount: usize) {
+        self.0.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Clone for Counter {
+    fn clone(&self) -> Self {
+        Counter(AtomicUsize::new(self.get()))
+    }
+}
+
+/// Snapshot of the settings a table is running with, returned by `config`.
+#[derive(Debug, PartialEq)]
+struct TableConfig {
+    size: usize,
+    load_factor: f64,
+    growth_factor: usize,
+    probe_strategy: ProbeStrategy,
+    hasher: &'static str,
+}
+
+/// How `find_slot` steps away from a key's home slot when the slot is taken.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ProbeStrategy {
+    /// Tries home, home + 1, home + 2, ...
+    Linear,
+    /// Tries home, home + 1, home + 3, home + 6, ... which only visits every slot when the table size
+    /// is a power of two, so tables using it are always sized up to one.
+    Quadratic,
+}
+
+impl ProbeStrategy {
+    /// Index of the `step`th slot tried for a key whose home slot is `home`.
+    fn index(&self, home: usize, step: usize, size: usize) -> usize {
+        match self {
+            ProbeStrategy::Linear => (home + step) % size,
+            ProbeStrategy::Quadratic => (home + step * (step + 1) / 2) % size,
+        }
+    }
+
+    /// Smallest table size of at least `size` that every probe sequence covers in full, or None if
+    /// that doesn't fit in a `usize`.
+    fn table_size(&self, size: usize) -> Option<usize> {
+        match self {
+            ProbeStrategy::Linear => Some(size),
+            ProbeStrategy::Quadratic => size.checked_next_power_of_two(),
+        }
+    }
+}
+
+/// What `put_with_policy` does when the key is already in the table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OverwritePolicy {
+    /// Overwrites the existing value, the same as `put`.
+    Replace,
+    /// Leaves the existing value in place.
+    Keep,
+    /// Leaves the existing value in place and returns an error.
+    Error,
+}
+
+#[derive(Debug, PartialEq)]
+enum PutResult {
+    /// The key wasn't in the table and has been added.
+    Inserted,
+    /// The key was in the table and its value has been overwritten.
+    Replaced,
+    /// The key was in the table and its value has been left alone.
+    Kept,
+    Error(&'static str),
 }
 
 // impl fmt::Display for HashTable {
@@ -29,189 +146,2192 @@ impl<K: Eq + Clone + Hash + Default, V: Clone + Default> HashTable<K, V> {
     fn new(size: usize) -> Self {
         HashTable {
             size,
-            table: vec![HashTableEntry { key: Default::default(), value: Default::default(), is_alive: false, has_been_used: false }; size],
+            table: vec![HashTableEntry { key: Default::default(), value: Default::default(), is_alive: false, has_been_used: false, generation: 0 }; size],
             current_size: 0,
+            negative: None,
+            probe_count: Counter::default(),
+            lookup_count: Counter::default(),
+            probe_strategy: ProbeStrategy::Linear,
+            load_factor: DEFAULT_LOAD_FACTOR,
+            auto_resize: true,
+            generation: 0,
         }
     }
 
     fn get(&self, key: &K) -> Option<&V> {
-        return match self.find_slot(key) {
-            Some(index) => {
-                if self.table[index].key == *key && self.table[index].is_alive {
-                    Some(&self.table[index].value)
-                } else {
-                    None
-                }
+        match self.find_slot(key) {
+            Some(index) if self.table[index].key == *key && self.table[index].is_alive => {
+                Some(&self.table[index].value)
             }
-            None => { None }
-        };
+            _ => { None }
+        }
+    }
+
+    /// Returns the value for `key`, calling `compute` with the key and storing its result first if
+    /// the key is not in the table. Returns an error if the result can't be stored because the table
+    /// is full.
+    fn get_or_compute<F: FnOnce(&K) -> V>(&mut self, key: K, compute: F) -> Result<&V, &'static str> {
+        if self.get(&key).is_none() {
+            let value = compute(&key);
+            self.put(key.clone(), value)?;
+        }
+        let index = self.find_slot(&key).unwrap();
+        Ok(&self.table[index].value)
+    }
+
+    /// Records `key` as known to be missing, so callers can avoid going back to the backing source.
+    /// Putting the key into the table clears the record.
+    fn put_negative(&mut self, key: K) {
+        let size = self.size;
+        self.negative.get_or_insert_with(|| Box::new(HashTable::new(size))).put(key, ()).unwrap();
+    }
+
+    /// Returns true if `key` has been recorded as known missing, false if nothing is known about it.
+    fn get_negative(&self, key: &K) -> bool {
+        match &self.negative {
+            Some(negative) => negative.get(key).is_some(),
+            None => false,
+        }
     }
 
-    /// Returns index of desired key, or index of first unused slot. Index of unused slot means the
-    /// key does not exist in the table, since they key would be found before an unused slot.
+    /// Returns index of desired key, or else the index of the first deleted slot passed on the way
+    /// to an unused slot, or of that unused slot if there was none. An unused slot means the key does
+    /// not exist in the table, since the key would be found before an unused slot.
     /// Table entry for returned index needs to be checked to see if the key matches.
-    /// TODO(reece): Is optional required here? Should never fully loop the end since the table should never get full
+    /// Returns None if every slot was probed without finding any of these, which should only happen
+    /// if the table has somehow been filled.
     fn find_slot(&self, key: &K) -> Option<usize> {
-        let hash = self.hash(key);
-        let mut index = hash as usize % self.size;
+        let home = self.home_slot(key);
+        let mut index = home;
+        let mut probes = 1;
+        let mut first_deleted = None;
 
-        // If a slot hasn't been used yet, then the key cannot be further therefore it isn't in the map
-        while self.table[index].has_been_used && self.table[index].key != *key && self.table[index].is_alive == true {
-            index = (index + 1) % self.size;
+        // If a slot hasn't been used yet, then the key cannot be further therefore it isn't in the map.
+        // Deleted slots are probed past, since the key may have been put after the deleted entry.
+        while self.table[index].has_been_used {
+            if self.table[index].is_alive {
+                if self.table[index].key == *key {
+                    self.record_probes(probes);
+                    return Some(index);
+                }
+            } else if first_deleted.is_none() {
+                first_deleted = Some(index);
+            }
+            if probes == self.size {
+                self.record_probes(probes);
+                return first_deleted;
+            }
+            index = self.probe_strategy.index(home, probes, self.size);
+            probes += 1;
         }
 
-        return Some(index);
+        self.record_probes(probes);
+        Some(first_deleted.unwrap_or(index))
+    }
+
+    fn record_probes(&self, probes: usize) {
+        self.probe_count.add(probes);
+        self.lookup_count.add(1);
     }
 
     fn resize(&mut self) {
-        let new_size = self.size * 2;
+        self.rehash(self.size * GROWTH_FACTOR);
+    }
 
-        let new_table = vec![HashTableEntry { key: Default::default(), value: Default::default(), is_alive: false, has_been_used: false }; new_size];
-        let mut old_table = mem::replace(&mut self.table, new_table);
-        let mut hash_count = 0;
+    /// Rebuilds the table with `new_size` slots, or more if the probe strategy needs it, reinserting
+    /// only the live entries.
+    fn rehash(&mut self, new_size: usize) {
+        let new_size = self.probe_strategy.table_size(new_size).expect("table size overflows usize");
+        let new_table = vec![HashTableEntry { key: Default::default(), value: Default::default(), is_alive: false, has_been_used: false, generation: 0 }; new_size];
+        let old_table = mem::replace(&mut self.table, new_table);
         self.size = new_size;
         self.current_size = 0;
 
-        let mut index = 0;
-        let mut len = old_table.len();
-        while index < len {
-            if old_table[index].is_alive {
-                let moved = old_table.remove(index);
-                self.put(moved.key, moved.value).unwrap();
-                len = old_table.len();
-                hash_count += 1;
-            } else {
-                index += 1;
-            }
+        for entry in old_table.into_iter().filter(|entry| entry.is_alive) {
+            let index = self.find_slot(&entry.key).unwrap();
+            self.table[index] = entry;
+            self.current_size += 1;
         }
-        self.current_size = hash_count;
     }
 
-    fn put(&mut self, key: K, val: V) -> Result<(), &str> {
-        let index = self.find_slot(&key).unwrap();
+    /// Changes how collisions are probed, rehashing so existing entries can be found with the new
+    /// strategy. Switching to `Quadratic` grows the table to the next power of two.
+    fn set_probe_strategy(&mut self, strategy: ProbeStrategy) {
+        self.probe_strategy = strategy;
+        self.rehash(self.size);
+    }
+
+    /// Number of entries at which the table resizes.
+    fn resize_threshold(&self) -> usize {
+        (self.size as f64 * self.load_factor) as usize
+    }
+
+    fn config(&self) -> TableConfig {
+        TableConfig {
+            size: self.size,
+            load_factor: self.load_factor,
+            growth_factor: GROWTH_FACTOR,
+            probe_strategy: self.probe_strategy,
+            hasher: std::any::type_name::<DefaultHasher>(),
+        }
+    }
+
+    /// Turns automatic resizing on put on or off. Turning it back on doesn't resize straight away.
+    fn set_auto_resize(&mut self, enabled: bool) {
+        self.auto_resize = enabled;
+    }
+
+    /// Whether the table has reached its resize threshold without resizing, which can only happen
+    /// while automatic resizing is turned off.
+    fn resize_overdue(&self) -> bool {
+        self.current_size >= self.resize_threshold()
+    }
+
+    /// Smallest size that can hold `entries` at `load_factor` without resizing, or an error if that
+    /// doesn't fit in a `usize`.
+    fn size_for_load_factor(entries: usize, load_factor: f64) -> Result<usize, &'static str> {
+        let estimate = (entries.checked_add(1).ok_or("table is too large")? as f64 / load_factor).ceil();
+        if estimate >= usize::MAX as f64 {
+            return Err("table is too large");
+        }
+        let mut size = estimate as usize;
+        while (size as f64 * load_factor) as usize <= entries {
+            size = size.checked_add(1).ok_or("table is too large")?;
+        }
+        Ok(size)
+    }
+
+    /// Copies the table into a new one that resizes at `factor` instead, sized so the live entries
+    /// fit without resizing. The copy keeps the probe strategy and whether it resizes automatically.
+    fn clone_with_load_factor(&self, factor: f64) -> Self {
+        assert!(factor > 0.0 && factor < 1.0, "load factor must be between 0 and 1");
+        let live = self.table.iter().filter(|entry| entry.is_alive).count();
+        let size = Self::size_for_load_factor(live, factor).ok().and_then(|size| self.probe_strategy.table_size(size));
+        let mut copy = HashTable::new(size.expect("load factor is too small for the number of entries"));
+        copy.load_factor = factor;
+        copy.probe_strategy = self.probe_strategy;
+        copy.auto_resize = self.auto_resize;
+        copy.negative = self.negative.clone();
+        for entry in self.table.iter().filter(|entry| entry.is_alive) {
+            copy.put(entry.key.clone(), entry.value.clone()).unwrap();
+        }
+        copy
+    }
+
+    fn put(&mut self, key: K, val: V) -> Result<(), &'static str> {
+        match self.put_with_policy(key, val, OverwritePolicy::Replace) {
+            PutResult::Error(message) => Err(message),
+            _ => Ok(()),
+        }
+    }
+
+    /// Puts `value` for `key`, using `policy` to decide what happens if the key is already in the
+    /// table.
+    fn put_with_policy(&mut self, key: K, value: V, policy: OverwritePolicy) -> PutResult {
+        let index = match self.find_slot(&key) {
+            Some(index) => index,
+            None => return PutResult::Error("table is full"),
+        };
+        let exists = self.table[index].key == key && self.table[index].is_alive;
+        if exists {
+            match policy {
+                OverwritePolicy::Replace => {}
+                OverwritePolicy::Keep => return PutResult::Kept,
+                OverwritePolicy::Error => return PutResult::Error("key already exists"),
+            }
+        }
+
+        if let Some(negative) = &mut self.negative {
+            negative.delete(&key);
+        }
+        self.generation += 1;
         self.table[index] = HashTableEntry {
-            key: key.clone(),
-            value: val,
+            key,
+            value,
             is_alive: true,
             has_been_used: true,
+            generation: self.generation,
         };
+        if exists {
+            return PutResult::Replaced;
+        }
+
         self.current_size += 1;
-        if self.current_size >= self.size / 2 {
+        if self.auto_resize && self.current_size >= self.resize_threshold() {
             self.resize();
         }
-        return Ok(());
+        PutResult::Inserted
     }
 
     fn delete(&mut self, key: &K) {
-        let index = self.find_slot(key).unwrap();
-        if self.table[index].key == *key {
-            self.table[index].is_alive = false;
+        if let Some(index) = self.find_slot(key) {
+            if self.table[index].key == *key && self.table[index].is_alive {
+                self.generation += 1;
+                self.table[index].is_alive = false;
+                self.table[index].generation = self.generation;
+            }
         }
     }
 
-    fn hash(&self, key: &K) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        key.hash(&mut hasher);
-        return hasher.finish();
+    /// Puts a value for every key in `keys`, producing each value by calling `loader` with its key.
+    /// Stops with an error at the first key that can't be put because the table is full.
+    fn warm<I: IntoIterator<Item = K>, F: FnMut(&K) -> V>(&mut self, keys: I, mut loader: F) -> Result<(), &'static str> {
+        for key in keys {
+            let value = loader(&key);
+            self.put(key, value)?;
+        }
+        Ok(())
     }
-}
 
-fn main() {
-    let mut hash_map = HashTable::new(2);
+    /// Average number of slots inspected per lookup over the table's lifetime, including the
+    /// lookups done while resizing. Returns 0 if nothing has been looked up yet.
+    fn avg_probes_per_op(&self) -> f64 {
+        if self.lookup_count.get() == 0 {
+            return 0.0;
+        }
+        self.probe_count.get() as f64 / self.lookup_count.get() as f64
+    }
 
-    hash_map.put(1, 1).unwrap();
-    println!("{}", hash_map.get(&1).unwrap());
-    hash_map.put(1, 11).unwrap();
-    println!("{}", hash_map.get(&1).unwrap());
-    hash_map.delete(&1);
-    hash_map.put(1, 11).unwrap();
-    println!("{}", hash_map.get(&1).unwrap());
-    hash_map.put(3, 3).unwrap();
-    println!("{}", hash_map.get(&3).unwrap());
-    hash_map.put(5, 5).unwrap();
-    println!("{}", hash_map.get(&5).unwrap());
-    hash_map.put(-1, -1).unwrap();
-    println!("{}", hash_map.get(&-1).unwrap());
-}
+    /// Fraction of used slots that are tombstones left behind by deletes, from 0 (none) to 1.
+    fn fragmentation(&self) -> f64 {
+        let live = self.table.iter().filter(|entry| entry.is_alive).count();
+        let tombstones = self.table.iter().filter(|entry| entry.has_been_used && !entry.is_alive).count();
+        if live + tombstones == 0 {
+            return 0.0;
+        }
+        tombstones as f64 / (live + tombstones) as f64
+    }
 
-#[cfg(test)]
-mod tests {
-    use crate::HashTable;
+    /// Number of different home slots the live keys hash to. Compared against `current_size`, this
+    /// shows how many keys are sharing a home slot with another key.
+    fn distinct_home_slots(&self) -> usize {
+        let mut homes = vec![false; self.size];
+        for entry in self.table.iter().filter(|entry| entry.is_alive) {
+            homes[self.home_slot(&entry.key)] = true;
+        }
+        homes.into_iter().filter(|&home| home).count()
+    }
 
-    #[test]
-    fn test_get_no_keys() {
-        let hash_table: HashTable<String, i32> = HashTable::new(10);
-        let result = hash_table.get(&"Key".to_string());
-        assert_eq!(result, None);
+    /// Slot a key is tried in first, before any probing.
+    fn home_slot(&self, key: &K) -> usize {
+        self.hash(key) as usize % self.size
     }
 
-    #[test]
-    fn test_put_and_get() {
-        let mut hash_table = HashTable::new(10);
-        hash_table.put("Test".to_string(), 3).unwrap();
-        let result = hash_table.get(&"Test".to_string());
-        assert_eq!(result, Some(&3));
+    /// Sizes a table created with `HashTable::new(initial)` goes through while `inserts` new keys
+    /// are put into it, starting with `initial` itself.
+    fn resize_sizes(initial: usize, inserts: usize) -> Vec<usize> {
+        let mut sizes = vec![initial];
+        let mut size = initial;
+        for current_size in 1..=inserts {
+            if current_size >= (size as f64 * DEFAULT_LOAD_FACTOR) as usize {
+                size *= GROWTH_FACTOR;
+                sizes.push(size);
+            }
+        }
+        sizes
     }
 
-    #[test]
-    fn test_put_and_get_multiple_keys() {
-        let mut hash_table = HashTable::new(10);
-        let result = hash_table.put("Key".to_string(), 1);
-        assert!(result.is_ok());
+    /// Live keys sitting at least `min_probe` probe steps away from their home slot.
+    fn slow_keys(&self, min_probe: usize) -> Vec<&K> {
+        self.table.iter().enumerate()
+            .filter(|(index, entry)| entry.is_alive && self.probe_distance(*index) >= min_probe)
+            .map(|(_, entry)| &entry.key)
+            .collect()
+    }
 
-        let result = hash_table.get(&"Key".to_string());
-        assert_eq!(result, Some(&1));
+    /// Number of probe steps taken from its home slot to reach the entry at `index`.
+    fn probe_distance(&self, index: usize) -> usize {
+        let home = self.home_slot(&self.table[index].key);
+        (0..self.size)
+            .find(|&step| self.probe_strategy.index(home, step, self.size) == index)
+            .unwrap_or(0)
+    }
 
-        let result = hash_table.put("Test".to_string(), 3);
-        assert!(result.is_ok());
-        let result = hash_table.get(&"Test".to_string());
-        assert_eq!(result, Some(&3));
+    /// Rehashes the table, doubling its size until no live entry is more than `max_probe` steps from
+    /// its home slot. Gives up with an error once the table would grow past `MAX_REBUILD_SIZE`, in
+    /// which case the table is rehashed back to its original size.
+    fn rebuild_under_max_probe(&mut self, max_probe: usize) -> Result<(), ()> {
+        let original_size = self.size;
+        let mut new_size = self.size;
+        loop {
+            self.rehash(new_size);
+            if self.max_probe_distance() <= max_probe {
+                return Ok(());
+            }
+            new_size = self.size * GROWTH_FACTOR;
+            if new_size > MAX_REBUILD_SIZE {
+                self.rehash(original_size);
+                return Err(());
+            }
+        }
+    }
 
-        let result = hash_table.put("Test2".to_string(), 5);
-        assert!(result.is_ok());
-        let result = hash_table.get(&"Test2".to_string());
-        assert_eq!(result, Some(&5));
+    /// Largest probe distance of any live entry, or 0 for an empty table.
+    fn max_probe_distance(&self) -> usize {
+        (0..self.size)
+            .filter(|&index| self.table[index].is_alive)
+            .map(|index| self.probe_distance(index))
+            .max()
+            .unwrap_or(0)
     }
 
-    #[test]
-    fn test_put() {
-        let mut hash_table = HashTable::new(10);
-        let result = hash_table.put("Test".to_string(), 10);
-        assert!(result.is_ok());
+    /// Smallest table size at which every live key has a home slot to itself, so nothing would need
+    /// to probe. Sizes up to `COLLISION_FREE_SEARCH_LIMIT` are tried before giving up with None.
+    fn min_collision_free_size(&self) -> Option<usize> {
+        let hashes: Vec<u64> = self.table.iter()
+            .filter(|entry| entry.is_alive)
+            .map(|entry| self.hash(&entry.key))
+            .collect();
+        (hashes.len().max(1)..=COLLISION_FREE_SEARCH_LIMIT).find(|&size| {
+            let mut homes: Vec<usize> = hashes.iter().map(|&hash| hash as usize % size).collect();
+            homes.sort_unstable();
+            homes.dedup();
+            homes.len() == hashes.len()
+        })
     }
 
-    #[test]
-    fn test_multiple_puts_different_keys() {
-        let mut hash_table = HashTable::new(4);
-        let result = hash_table.put("Test".to_string(), 10);
-        assert!(result.is_ok());
-        let result = hash_table.put("Test2".to_string(), 10);
-        assert!(result.is_ok());
-        let result = hash_table.put("Test3".to_string(), 10);
-        assert!(result.is_ok());
-        let result = hash_table.put("Test4".to_string(), 10);
-        assert!(result.is_ok());
+    /// Puts pairs pulled from `gen` until it returns None, returning how many were put.
+    fn fill_from<F: FnMut() -> Option<(K, V)>>(&mut self, mut gen: F) -> usize {
+        let mut count = 0;
+        while let Some((key, value)) = gen() {
+            if self.put(key, value).is_ok() {
+                count += 1;
+            }
+        }
+        count
     }
 
-    #[test]
-    fn test_delete() {
-        let mut hash_table: HashTable<String, i32> = HashTable::new(10);
-        let result = hash_table.put("Test".to_string(), 10);
-        assert!(result.is_ok());
-        hash_table.delete(&"Test".to_string());
-        let result = hash_table.get(&"Test".to_string());
-        assert_eq!(result, None);
+    /// Bytes taken up by each slot, for estimating a table's allocation before creating it.
+    fn entry_byte_size() -> usize {
+        mem::size_of::<HashTableEntry<K, V>>()
     }
 
-    #[test]
-    fn test_resize() {
-        let mut hash_table: HashTable<String, i32> = HashTable::new(2);
-        assert_eq!(hash_table.size, 2);
-        let result = hash_table.put("Test".to_string(), 10);
-        assert!(result.is_ok());
-        assert_eq!(hash_table.size, 4);
-        assert_eq!(hash_table.current_size, 1);
-        let result = hash_table.get(&"Test".to_string());
-        assert_eq!(result, Some(&10));
+    /// Live entries ordered by the hash of their key, which is the same for any run regardless of
+    /// where the entries sit in the table.
+    fn iter_hash_sorted(&self) -> Vec<(&K, &V)> {
+        let mut entries: Vec<(&K, &V)> = self.table.iter()
+            .filter(|entry| entry.is_alive)
+            .map(|entry| (&entry.key, &entry.value))
+            .collect();
+        entries.sort_by_key(|(key, _)| self.hash(key));
+        entries
+    }
+
+    /// Consumes the table, keeping only the live pairs in an allocation no bigger than they need.
+    fn into_boxed_pairs(self) -> Box<[(K, V)]> {
+        self.table.into_iter()
+            .filter(|entry| entry.is_alive)
+            .map(|entry| (entry.key, entry.value))
+            .collect::<Vec<_>>()
+            .into_boxed_slice()
+    }
+
+    /// Current generation of the table, to pass to `changed_since` later.
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Generation at which the live entry for `key` was last changed, or None if the key isn't in the
+    /// table.
+    fn entry_generation(&self, key: &K) -> Option<u64> {
+        match self.find_slot(key) {
+            Some(index) if self.table[index].key == *key && self.table[index].is_alive => {
+                Some(self.table[index].generation)
+            }
+            _ => { None }
+        }
+    }
+
+    /// Keys that have been put, updated or deleted since generation `gen`. Deletes are found through
+    /// the tombstones they leave behind, so a delete is no longer reported once a rehash has dropped
+    /// its tombstone. Entries discarded by `prefault` leave no tombstones and are never reported.
+    fn changed_since(&self, gen: u64) -> Vec<&K> {
+        self.table.iter()
+            .filter(|entry| entry.has_been_used && entry.generation > gen)
+            // A key deleted and then put again can leave a tombstone behind its live entry.
+            .filter(|entry| entry.is_alive || self.slot_of(&entry.key).is_none())
+            .map(|entry| &entry.key)
+            .collect()
+    }
+
+    /// Writes to every slot so the memory behind the table is faulted in before it's needed,
+    /// leaving the table empty at its current size. Anything already in the table is discarded
+    /// without leaving tombstones, so `changed_since` won't report the discarded keys, although the
+    /// generation still moves on.
+    fn prefault(&mut self) {
+        self.generation += 1;
+        for entry in self.table.iter_mut() {
+            *entry = HashTableEntry { key: Default::default(), value: Default::default(), is_alive: true, has_been_used: true, generation: 0 };
+            entry.is_alive = false;
+            entry.has_been_used = false;
+        }
+        self.current_size = 0;
+    }
+
+    /// One flag per slot, true where the slot holds a live entry.
+    fn occupancy_bitmap(&self) -> Vec<bool> {
+        self.table.iter().map(|entry| entry.is_alive).collect()
+    }
+
+    /// Predicts a `put` of `key` without changing the table, returning the slot the key would be
+    /// written to and whether the put would then resize the table (moving the key elsewhere).
+    /// Returns None if there is no slot for the key, where a real put would return an error.
+    fn dry_run_put(&self, key: &K) -> Option<(usize, bool)> {
+        let index = self.find_slot(key)?;
+        let exists = self.table[index].key == *key && self.table[index].is_alive;
+        let resizes = !exists && self.auto_resize && self.current_size + 1 >= self.resize_threshold();
+        Some((index, resizes))
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Puts every live entry of `other` into this table, overwriting existing values, and returns
+    /// the keys that were already in this table. Stops with an error at the first entry that can't
+    /// be put because the table is full, leaving the entries before it merged.
+    fn merge_report(&mut self, other: HashTable<K, V>) -> Result<Vec<K>, &'static str> {
+        let mut conflicts = Vec::new();
+        for entry in other.table.into_iter().filter(|entry| entry.is_alive) {
+            if self.contains_key(&entry.key) {
+                conflicts.push(entry.key.clone());
+            }
+            self.put(entry.key, entry.value)?;
+        }
+        Ok(conflicts)
+    }
+
+    /// Standard deviation of the probe distances of all live entries, or 0 for an empty table.
+    fn probe_distance_stddev(&self) -> f64 {
+        let distances: Vec<f64> = (0..self.size)
+            .filter(|&index| self.table[index].is_alive)
+            .map(|index| self.probe_distance(index) as f64)
+            .collect();
+        if distances.is_empty() {
+            return 0.0;
+        }
+        let mean = distances.iter().sum::<f64>() / distances.len() as f64;
+        let variance = distances.iter().map(|distance| (distance - mean).powi(2)).sum::<f64>() / distances.len() as f64;
+        variance.sqrt()
+    }
+
+    /// Builds a table pairing each key in `keys` with the value at the same position in `values`.
+    fn from_columns(keys: Vec<K>, values: Vec<V>) -> Result<Self, &'static str> {
+        if keys.len() != values.len() {
+            return Err("keys and values have different lengths");
+        }
+        let mut hash_table = HashTable::new(Self::size_for_load_factor(keys.len(), DEFAULT_LOAD_FACTOR)?);
+        for (key, value) in keys.into_iter().zip(values) {
+            hash_table.put(key, value).unwrap();
+        }
+        Ok(hash_table)
+    }
+
+    /// Number of live entries a rehash into a table of the same size would leave in their current
+    /// slot.
+    fn stable_slot_count(&self) -> usize {
+        self.simulate_rehash(self.size).into_iter().filter(|(old, new)| old == new).count()
+    }
+
+    /// Works out where `rehash(new_size)` would put each live entry, without moving anything.
+    /// Returns the current and new slot index of each live entry.
+    fn simulate_rehash(&self, new_size: usize) -> Vec<(usize, usize)> {
+        let mut occupied = vec![false; new_size];
+        let mut moves = Vec::new();
+        for (index, entry) in self.table.iter().enumerate().filter(|(_, entry)| entry.is_alive) {
+            // Rehashing drops tombstones and duplicates, so the first unoccupied slot is where the
+            // entry would go.
+            let home = self.hash(&entry.key) as usize % new_size;
+            let mut new_index = home;
+            let mut step = 0;
+            while occupied[new_index] {
+                step += 1;
+                new_index = self.probe_strategy.index(home, step, new_size);
+            }
+            occupied[new_index] = true;
+            moves.push((index, new_index));
+        }
+        moves
+    }
+
+    /// Live key whose hash is numerically closest to `target`, or None for an empty table.
+    fn key_nearest_hash(&self, target: u64) -> Option<&K> {
+        self.table.iter()
+            .filter(|entry| entry.is_alive)
+            .min_by_key(|entry| self.hash(&entry.key).abs_diff(target))
+            .map(|entry| &entry.key)
+    }
+
+    /// Sets the value of every live entry to `value`, leaving the keys where they are.
+    fn fill_values(&mut self, value: V) {
+        self.generation += 1;
+        for entry in self.table.iter_mut().filter(|entry| entry.is_alive) {
+            entry.value = value.clone();
+            entry.generation = self.generation;
+        }
+    }
+
+    /// Calls `f` with each live key and a mutable reference to its value.
+    fn map_values_in_place<F: FnMut(&K, &mut V)>(&mut self, mut f: F) {
+        self.generation += 1;
+        for entry in self.table.iter_mut().filter(|entry| entry.is_alive) {
+            f(&entry.key, &mut entry.value);
+            entry.generation = self.generation;
+        }
+    }
+
+    /// Grows the table so `additional` more keys can be put without it resizing. Returns an error
+    /// without changing the table if a table that big couldn't be allocated.
+    fn reserve(&mut self, additional: usize) -> Result<(), &'static str> {
+        let needed = self.current_size.checked_add(additional).ok_or("table is too large")?;
+        if self.resize_threshold() > needed {
+            return Ok(());
+        }
+        let size = Self::size_for_load_factor(needed, self.load_factor)?;
+        let size = self.probe_strategy.table_size(size).ok_or("table is too large")?;
+        match size.checked_mul(Self::entry_byte_size()) {
+            Some(bytes) if bytes <= isize::MAX as usize => {}
+            _ => return Err("table is too large"),
+        }
+        self.rehash(size);
+        Ok(())
+    }
+
+    /// Reserves room for `per_second` new keys a second arriving for `seconds` seconds. Returns an
+    /// error without changing the table if that many keys can't be reserved.
+    fn reserve_for_rate(&mut self, per_second: usize, seconds: usize) -> Result<(), &'static str> {
+        let additional = per_second.checked_mul(seconds).ok_or("table is too large")?;
+        self.reserve(additional)
+    }
+
+    /// Whether the live entry for `key` sits in its home slot, or None if the key isn't in the table.
+    fn is_at_home(&self, key: &K) -> Option<bool> {
+        match self.find_slot(key) {
+            Some(index) if self.table[index].key == *key && self.table[index].is_alive => {
+                Some(index == self.home_slot(key))
+            }
+            _ => { None }
+        }
+    }
+
+    /// Slot holding the live entry for `key`, or None if the key isn't in the table.
+    fn slot_of(&self, key: &K) -> Option<usize> {
+        match self.find_slot(key) {
+            Some(index) if self.table[index].key == *key && self.table[index].is_alive => Some(index),
+            _ => { None }
+        }
+    }
+
+    /// Every live entry along with the index of the slot it's in.
+    fn entries_with_slots(&self) -> Vec<(&K, &V, usize)> {
+        self.table.iter().enumerate()
+            .filter(|(_, entry)| entry.is_alive)
+            .map(|(index, entry)| (&entry.key, &entry.value, index))
+            .collect()
+    }
+
+    /// Number of new keys that can be put before the next new key makes the table resize.
+    fn inserts_until_resize(&self) -> usize {
+        self.resize_threshold().saturating_sub(self.current_size + 1)
+    }
+
+    /// Puts every pair from `items`, with later values for a key overwriting earlier ones, and
+    /// returns how many pairs repeated a key seen earlier in `items`. Stops with an error at the first
+    /// pair that can't be put because the table is full.
+    fn insert_dedup<I: IntoIterator<Item = (K, V)>>(&mut self, items: I) -> Result<usize, &'static str> {
+        let mut seen: HashTable<K, ()> = HashTable::new(self.size);
+        let mut duplicates = 0;
+        for (key, value) in items {
+            if seen.contains_key(&key) {
+                duplicates += 1;
+            } else {
+                seen.put(key.clone(), ()).unwrap();
+            }
+            self.put(key, value)?;
+        }
+        Ok(duplicates)
+    }
+
+    /// Index of the first never used slot at or after `start`, wrapping around the end of the table,
+    /// or None if every slot has been used.
+    fn first_empty_from(&self, start: usize) -> Option<usize> {
+        (0..self.size)
+            .map(|step| (start % self.size + step) % self.size)
+            .find(|&index| !self.table[index].has_been_used)
+    }
+
+    /// Live keys with the smallest and largest hash, or None for an empty table.
+    fn hash_extremes(&self) -> Option<(&K, &K)> {
+        let live = || self.table.iter().filter(|entry| entry.is_alive);
+        let min = live().min_by_key(|entry| self.hash(&entry.key))?;
+        let max = live().max_by_key(|entry| self.hash(&entry.key))?;
+        Some((&min.key, &max.key))
+    }
+
+    /// Rehashes into a bigger table if the probe distances are spread out by more than
+    /// `BALANCE_STDDEV_THRESHOLD`, as happens when keys have clumped into a few long runs.
+    fn balance(&mut self) {
+        if self.probe_distance_stddev() > BALANCE_STDDEV_THRESHOLD {
+            self.rehash(self.size * GROWTH_FACTOR);
+        }
+    }
+
+    /// Splits the live entries into `buckets` groups by key hash, so the same key always ends up in
+    /// the same group whatever size the table is.
+    fn bucketize(&self, buckets: usize) -> Vec<Vec<(&K, &V)>> {
+        assert!(buckets > 0, "need at least one bucket");
+        let mut groups: Vec<Vec<(&K, &V)>> = (0..buckets).map(|_| Vec::new()).collect();
+        for entry in self.table.iter().filter(|entry| entry.is_alive) {
+            groups[(self.hash(&entry.key) % buckets as u64) as usize].push((&entry.key, &entry.value));
+        }
+        groups
+    }
+
+    /// Panics if the same key is live in more than one slot, which would mean probing has gone
+    /// wrong somewhere. Compares every pair of live slots, so is meant for tests and debug builds.
+    fn assert_no_duplicate_keys(&self) {
+        for (i, entry) in self.table.iter().enumerate().filter(|(_, entry)| entry.is_alive) {
+            for (j, other) in self.table.iter().enumerate().skip(i + 1).filter(|(_, other)| other.is_alive) {
+                if entry.key == other.key {
+                    panic!("key is live in both slot {} and slot {}", i, j);
+                }
+            }
+        }
+    }
+
+    /// Live keys in this table that aren't in `other`.
+    fn difference<'a>(&'a self, other: &'a HashTable<K, V>) -> Vec<&'a K> {
+        self.table.iter()
+            .filter(|entry| entry.is_alive && !other.contains_key(&entry.key))
+            .map(|entry| &entry.key)
+            .collect()
+    }
+
+    /// Live keys in both this table and `other`.
+    fn intersection<'a>(&'a self, other: &'a HashTable<K, V>) -> Vec<&'a K> {
+        self.table.iter()
+            .filter(|entry| entry.is_alive && other.contains_key(&entry.key))
+            .map(|entry| &entry.key)
+            .collect()
+    }
+
+    /// Live keys in exactly one of this table and `other`.
+    fn symmetric_difference<'a>(&'a self, other: &'a HashTable<K, V>) -> Vec<&'a K> {
+        let mut keys = self.difference(other);
+        keys.extend(other.difference(self));
+        keys
+    }
+
+    /// Whether every live key in this table is also live in `other`.
+    fn is_key_subset(&self, other: &HashTable<K, V>) -> bool {
+        self.table.iter()
+            .filter(|entry| entry.is_alive)
+            .all(|entry| other.contains_key(&entry.key))
+    }
+
+    /// Puts every pair received on `rx` until all its senders have been dropped, returning how many
+    /// were put. Blocks while the channel is empty but still open.
+    fn load_from_channel(&mut self, rx: mpsc::Receiver<(K, V)>) -> usize {
+        self.fill_from(|| rx.recv().ok())
+    }
+
+    /// Key and value in slot `index`, or None if the slot is empty, deleted or out of range.
+    fn entry_at(&self, index: usize) -> Option<(&K, &V)> {
+        self.table.get(index)
+            .filter(|entry| entry.is_alive)
+            .map(|entry| (&entry.key, &entry.value))
+    }
+
+    /// Iterates over the live entries whose key doesn't match `exclude`.
+    fn iter_excluding<'a, F: Fn(&K) -> bool + 'a>(&'a self, exclude: F) -> impl Iterator<Item = (&'a K, &'a V)> {
+        self.table.iter()
+            .filter(move |entry| entry.is_alive && !exclude(&entry.key))
+            .map(|entry| (&entry.key, &entry.value))
+    }
+
+    /// Puts each pair in `items` in order, returning for each one either Ok or the pair itself if
+    /// there was no slot for it. Only a table with automatic resizing turned off can run out of
+    /// slots.
+    fn put_batch(&mut self, items: Vec<(K, V)>) -> Vec<Result<(), (K, V)>> {
+        items.into_iter()
+            .map(|(key, value)| {
+                if self.find_slot(&key).is_none() {
+                    return Err((key, value));
+                }
+                self.put(key, value).unwrap();
+                Ok(())
+            })
+            .collect()
+    }
+
+    /// Number of live entries that would end up in a different slot if the table resized now.
+    fn entries_moving_on_resize(&self) -> usize {
+        self.simulate_rehash(self.size * GROWTH_FACTOR).into_iter().filter(|(old, new)| old != new).count()
+    }
+
+    fn hash(&self, key: &K) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Multimap use: each key holds a `Vec` of items.
+impl<K: Eq + Clone + Hash + Default, T: Clone> HashTable<K, Vec<T>> {
+    /// Appends `item` to the values for `key`, starting a new list if the key isn't in the table.
+    /// Returns an error if a new list is needed but the table is full.
+    fn push(&mut self, key: K, item: T) -> Result<(), &'static str> {
+        match self.find_slot(&key) {
+            Some(index) if self.table[index].key == key && self.table[index].is_alive => {
+                self.generation += 1;
+                self.table[index].value.push(item);
+                self.table[index].generation = self.generation;
+                Ok(())
+            }
+            _ => self.put(key, vec![item]),
+        }
+    }
+
+    /// Returns every item pushed for `key` in the order they were pushed, or nothing if the key isn't
+    /// in the table.
+    fn get_all(&self, key: &K) -> &[T] {
+        match self.get(key) {
+            Some(items) => items,
+            None => &[],
+        }
+    }
+}
+
+impl<K: Eq + Clone + Hash + Default, V: Eq + Clone + Hash + Default> HashTable<K, V> {
+    /// Groups the live keys by value, returning only the groups where more than one key shares a
+    /// value.
+    fn duplicate_value_groups(&self) -> Vec<Vec<&K>> {
+        let mut groups: HashTable<V, Vec<usize>> = HashTable::new(self.size);
+        for (index, entry) in self.table.iter().enumerate().filter(|(_, entry)| entry.is_alive) {
+            groups.push(entry.value.clone(), index).unwrap();
+        }
+        groups.table.iter()
+            .filter(|group| group.is_alive && group.value.len() > 1)
+            .map(|group| group.value.iter().map(|&index| &self.table[index].key).collect())
+            .collect()
+    }
+}
+
+impl<K: Eq + Clone + Hash + Default, V: Ord + Clone + Default> HashTable<K, V> {
+    /// Middle value of the live entries once sorted, or None for an empty table. With an even
+    /// number of entries this is the lower of the two middle values.
+    fn median_value(&self) -> Option<V> {
+        let mut values: Vec<&V> = self.table.iter()
+            .filter(|entry| entry.is_alive)
+            .map(|entry| &entry.value)
+            .collect();
+        if values.is_empty() {
+            return None;
+        }
+        values.sort();
+        Some(values[(values.len() - 1) / 2].clone())
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<K: Eq + Clone + Hash + Default + Send + Sync, V: Clone + Default + Send + Sync> HashTable<K, V> {
+    /// Same as `map_values_in_place`, but spreads the calls to `f` across rayon's thread pool.
+    fn par_map_values<F: Fn(&K, &mut V) + Send + Sync>(&mut self, f: F) {
+        self.generation += 1;
+        let generation = self.generation;
+        self.table.par_iter_mut()
+            .filter(|entry| entry.is_alive)
+            .for_each(|entry| {
+                f(&entry.key, &mut entry.value);
+                entry.generation = generation;
+            });
+    }
+
+    /// Counts the live entries matching `f`, checking the slots in parallel.
+    fn par_count_matching<F: Fn(&K, &V) -> bool + Sync>(&self, f: F) -> usize {
+        self.table.par_iter()
+            .filter(|entry| entry.is_alive && f(&entry.key, &entry.value))
+            .count()
+    }
+}
+
+impl<K: Eq + Clone + Hash + Default + fmt::Display, V: Clone + Default + fmt::Display> HashTable<K, V> {
+    /// Renders the live entries as a JSON object, with every key and value written as a string.
+    fn to_json(&self) -> String {
+        let entries: Vec<String> = self.table.iter()
+            .filter(|entry| entry.is_alive)
+            .map(|entry| format!("\"{}\":\"{}\"", escape_json(&entry.key.to_string()), escape_json(&entry.value.to_string())))
+            .collect();
+        format!("{{{}}}", entries.join(","))
+    }
+
+    /// Draws the slots in rows of `columns`, showing `.` for a never used slot, `x` for a deleted
+    /// entry and the key for a live entry.
+    fn to_grid(&self, columns: usize) -> String {
+        assert!(columns > 0, "grid needs at least one column");
+        let cells: Vec<String> = self.table.iter()
+            .map(|entry| {
+                if entry.is_alive {
+                    entry.key.to_string()
+                } else if entry.has_been_used {
+                    "x".to_string()
+                } else {
+                    ".".to_string()
+                }
+            })
+            .collect();
+        cells.chunks(columns)
+            .map(|row| row.join(" "))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+/// Escapes `text` so it can be placed between quotes in a JSON document.
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Tables are written as a map of their live entries, and read back by putting each entry into a
+/// new table.
+#[cfg(feature = "serde")]
+impl<K: Eq + Clone + Hash + Default + serde::Serialize, V: Clone + Default + serde::Serialize> serde::Serialize for HashTable<K, V> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(None)?;
+        for entry in self.table.iter().filter(|entry| entry.is_alive) {
+            map.serialize_entry(&entry.key, &entry.value)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, K: Eq + Clone + Hash + Default + serde::Deserialize<'de>, V: Clone + Default + serde::Deserialize<'de>> serde::Deserialize<'de> for HashTable<K, V> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TableVisitor<K, V>(std::marker::PhantomData<(K, V)>);
+
+        impl<'de, K: Eq + Clone + Hash + Default + serde::Deserialize<'de>, V: Clone + Default + serde::Deserialize<'de>> serde::de::Visitor<'de> for TableVisitor<K, V> {
+            type Value = HashTable<K, V>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a map of keys to values")
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                // The length comes from the input, so it's only trusted up to a point and the table
+                // grows as usual past that.
+                let entries = map.size_hint().unwrap_or(0).min(MAX_DESERIALIZE_SIZE_HINT);
+                let mut hash_table = HashTable::new(HashTable::<K, V>::size_for_load_factor(entries, DEFAULT_LOAD_FACTOR).unwrap());
+                while let Some((key, value)) = map.next_entry()? {
+                    hash_table.put(key, value).map_err(serde::de::Error::custom)?;
+                }
+                Ok(hash_table)
+            }
+        }
+
+        deserializer.deserialize_map(TableVisitor(std::marker::PhantomData))
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+impl<K, V> HashTable<K, V>
+where
+    K: Eq + Clone + Hash + Default + fmt::Debug + serde::Serialize + serde::de::DeserializeOwned,
+    V: Clone + Default + PartialEq + fmt::Debug + serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// Writes the table to JSON and reads it back, panicking unless the copy has exactly the same
+    /// live entries.
+    fn assert_serde_roundtrip(&self) {
+        let json = serde_json::to_string(self).unwrap();
+        let copy: HashTable<K, V> = serde_json::from_str(&json).unwrap();
+
+        let live = self.table.iter().filter(|entry| entry.is_alive).count();
+        assert_eq!(copy.table.iter().filter(|entry| entry.is_alive).count(), live);
+        for entry in self.table.iter().filter(|entry| entry.is_alive) {
+            assert_eq!(copy.get(&entry.key), Some(&entry.value), "value for {:?} changed", entry.key);
+        }
+    }
+}
+
+fn main() {
+    let mut hash_map = HashTable::new(2);
+
+    hash_map.put(1, 1).unwrap();
+    println!("{}", hash_map.get(&1).unwrap());
+    hash_map.put(1, 11).unwrap();
+    println!("{}", hash_map.get(&1).unwrap());
+    hash_map.delete(&1);
+    hash_map.put(1, 11).unwrap();
+    println!("{}", hash_map.get(&1).unwrap());
+    hash_map.put(3, 3).unwrap();
+    println!("{}", hash_map.get(&3).unwrap());
+    hash_map.put(5, 5).unwrap();
+    println!("{}", hash_map.get(&5).unwrap());
+    hash_map.put(-1, -1).unwrap();
+    println!("{}", hash_map.get(&-1).unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{HashTable, OverwritePolicy, ProbeStrategy, PutResult, TableConfig};
+    use std::hash::{Hash, Hasher};
+
+    /// Key whose hash ignores its contents, so every instance lands in the same home slot.
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    struct Colliding(i32);
+
+    impl Hash for Colliding {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            0.hash(state);
+        }
+    }
+
+    #[test]
+    fn test_get_no_keys() {
+        let hash_table: HashTable<String, i32> = HashTable::new(10);
+        let result = hash_table.get(&"Key".to_string());
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_put_and_get() {
+        let mut hash_table = HashTable::new(10);
+        hash_table.put("Test".to_string(), 3).unwrap();
+        let result = hash_table.get(&"Test".to_string());
+        assert_eq!(result, Some(&3));
+    }
+
+    #[test]
+    fn test_put_and_get_multiple_keys() {
+        let mut hash_table = HashTable::new(10);
+        let result = hash_table.put("Key".to_string(), 1);
+        assert!(result.is_ok());
+
+        let result = hash_table.get(&"Key".to_string());
+        assert_eq!(result, Some(&1));
+
+        let result = hash_table.put("Test".to_string(), 3);
+        assert!(result.is_ok());
+        let result = hash_table.get(&"Test".to_string());
+        assert_eq!(result, Some(&3));
+
+        let result = hash_table.put("Test2".to_string(), 5);
+        assert!(result.is_ok());
+        let result = hash_table.get(&"Test2".to_string());
+        assert_eq!(result, Some(&5));
+    }
+
+    #[test]
+    fn test_put() {
+        let mut hash_table = HashTable::new(10);
+        let result = hash_table.put("Test".to_string(), 10);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_multiple_puts_different_keys() {
+        let mut hash_table = HashTable::new(4);
+        let result = hash_table.put("Test".to_string(), 10);
+        assert!(result.is_ok());
+        let result = hash_table.put("Test2".to_string(), 10);
+        assert!(result.is_ok());
+        let result = hash_table.put("Test3".to_string(), 10);
+        assert!(result.is_ok());
+        let result = hash_table.put("Test4".to_string(), 10);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut hash_table: HashTable<String, i32> = HashTable::new(10);
+        let result = hash_table.put("Test".to_string(), 10);
+        assert!(result.is_ok());
+        hash_table.delete(&"Test".to_string());
+        let result = hash_table.get(&"Test".to_string());
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut hash_table: HashTable<String, i32> = HashTable::new(2);
+        assert_eq!(hash_table.size, 2);
+        let result = hash_table.put("Test".to_string(), 10);
+        assert!(result.is_ok());
+        assert_eq!(hash_table.size, 4);
+        assert_eq!(hash_table.current_size, 1);
+        let result = hash_table.get(&"Test".to_string());
+        assert_eq!(result, Some(&10));
+    }
+
+    #[test]
+    fn test_get_or_compute() {
+        let mut hash_table: HashTable<String, usize> = HashTable::new(10);
+        let mut calls = 0;
+
+        let result = *hash_table.get_or_compute("Test".to_string(), |key| { calls += 1; key.len() }).unwrap();
+        assert_eq!(result, 4);
+        let result = *hash_table.get_or_compute("Test".to_string(), |key| { calls += 1; key.len() }).unwrap();
+        assert_eq!(result, 4);
+        assert_eq!(calls, 1);
+
+        let result = *hash_table.get_or_compute("Test2".to_string(), |key| { calls += 1; key.len() }).unwrap();
+        assert_eq!(result, 5);
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn test_negative_cache() {
+        let mut hash_table: HashTable<String, i32> = HashTable::new(10);
+        hash_table.put_negative("Missing".to_string());
+        assert!(hash_table.get_negative(&"Missing".to_string()));
+        assert!(!hash_table.get_negative(&"Unknown".to_string()));
+
+        hash_table.put("Missing".to_string(), 1).unwrap();
+        assert!(!hash_table.get_negative(&"Missing".to_string()));
+    }
+
+    #[test]
+    fn test_warm() {
+        let mut hash_table: HashTable<String, usize> = HashTable::new(2);
+        let keys = vec!["Key".to_string(), "Test".to_string(), "Test2".to_string()];
+        hash_table.warm(keys.clone(), |key| key.len()).unwrap();
+
+        let mut calls = 0;
+        for key in keys {
+            let expected = key.len();
+            let result = *hash_table.get_or_compute(key, |_| { calls += 1; 0 }).unwrap();
+            assert_eq!(result, expected);
+        }
+        assert_eq!(calls, 0);
+    }
+
+    #[test]
+    fn test_avg_probes_per_op() {
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(16);
+        assert_eq!(hash_table.avg_probes_per_op(), 0.0);
+        for i in 0..4 {
+            hash_table.put(Colliding(i), i).unwrap();
+        }
+        for i in 0..4 {
+            assert_eq!(hash_table.get(&Colliding(i)), Some(&i));
+        }
+        assert!(hash_table.avg_probes_per_op() > 1.0);
+    }
+
+    #[test]
+    fn test_table_is_sync() {
+        fn assert_sync<T: Sync + std::panic::RefUnwindSafe>() {}
+        assert_sync::<HashTable<String, i32>>();
+
+        let mut hash_table = HashTable::new(2);
+        hash_table.put(1, 10).unwrap();
+        let hash_table = &hash_table;
+        std::thread::scope(|scope| {
+            scope.spawn(|| assert_eq!(hash_table.get(&1), Some(&10)));
+            scope.spawn(|| assert_eq!(hash_table.get(&1), Some(&10)));
+        });
+        assert!(hash_table.avg_probes_per_op() >= 1.0);
+    }
+
+    #[test]
+    fn test_set_probe_strategy() {
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(16);
+        for i in 0..6 {
+            hash_table.put(Colliding(i), i).unwrap();
+        }
+        hash_table.set_probe_strategy(ProbeStrategy::Quadratic);
+        assert_eq!(hash_table.probe_strategy, ProbeStrategy::Quadratic);
+        assert_eq!(hash_table.current_size, 6);
+        for i in 0..6 {
+            assert_eq!(hash_table.get(&Colliding(i)), Some(&i));
+        }
+    }
+
+    #[test]
+    fn test_to_json() {
+        let mut hash_table: HashTable<String, i32> = HashTable::new(10);
+        assert_eq!(hash_table.to_json(), "{}");
+        hash_table.put("Say \"hi\"".to_string(), 1).unwrap();
+        assert_eq!(hash_table.to_json(), r#"{"Say \"hi\"":"1"}"#);
+
+        hash_table.put("Test".to_string(), 2).unwrap();
+        let json = hash_table.to_json();
+        assert!(json == r#"{"Say \"hi\"":"1","Test":"2"}"# || json == r#"{"Test":"2","Say \"hi\"":"1"}"#);
+    }
+
+    #[test]
+    fn test_fragmentation() {
+        let mut hash_table: HashTable<String, i32> = HashTable::new(16);
+        assert_eq!(hash_table.fragmentation(), 0.0);
+        for key in ["A", "B", "C", "D"].iter() {
+            hash_table.put(key.to_string(), 1).unwrap();
+        }
+        hash_table.delete(&"A".to_string());
+        assert_eq!(hash_table.fragmentation(), 0.25);
+
+        hash_table.rehash(hash_table.size);
+        assert_eq!(hash_table.fragmentation(), 0.0);
+        assert_eq!(hash_table.get(&"B".to_string()), Some(&1));
+    }
+
+    #[test]
+    fn test_push_and_get_all() {
+        let mut hash_table: HashTable<String, Vec<i32>> = HashTable::new(2);
+        assert!(hash_table.get_all(&"Key".to_string()).is_empty());
+        hash_table.push("Key".to_string(), 1).unwrap();
+        hash_table.push("Test".to_string(), 10).unwrap();
+        hash_table.push("Key".to_string(), 2).unwrap();
+        hash_table.push("Key".to_string(), 3).unwrap();
+        assert_eq!(hash_table.get_all(&"Key".to_string()), &[1, 2, 3]);
+        assert_eq!(hash_table.get_all(&"Test".to_string()), &[10]);
+    }
+
+    #[test]
+    fn test_distinct_home_slots() {
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(16);
+        assert_eq!(hash_table.distinct_home_slots(), 0);
+        for i in 0..3 {
+            hash_table.put(Colliding(i), i).unwrap();
+        }
+        assert_eq!(hash_table.distinct_home_slots(), 1);
+        assert!(hash_table.distinct_home_slots() < hash_table.current_size);
+    }
+
+    #[test]
+    fn test_find_slot_full_table() {
+        let mut hash_table: HashTable<i32, i32> = HashTable::new(4);
+        for (i, entry) in hash_table.table.iter_mut().enumerate() {
+            entry.key = i as i32;
+            entry.is_alive = true;
+            entry.has_been_used = true;
+        }
+        assert_eq!(hash_table.find_slot(&10), None);
+        assert_eq!(hash_table.get(&10), None);
+        assert_eq!(hash_table.put(10, 10), Err("table is full"));
+        assert_eq!(hash_table.get(&2), Some(&0));
+    }
+
+    #[test]
+    fn test_duplicate_value_groups() {
+        let mut hash_table: HashTable<String, i32> = HashTable::new(16);
+        hash_table.put("A".to_string(), 1).unwrap();
+        hash_table.put("B".to_string(), 2).unwrap();
+        hash_table.put("C".to_string(), 1).unwrap();
+        hash_table.put("D".to_string(), 3).unwrap();
+        hash_table.put("E".to_string(), 3).unwrap();
+
+        let mut groups: Vec<Vec<&String>> = hash_table.duplicate_value_groups();
+        for group in groups.iter_mut() {
+            group.sort();
+        }
+        groups.sort();
+        assert_eq!(groups, vec![vec!["A", "C"], vec!["D", "E"]]);
+    }
+
+    #[test]
+    fn test_resize_sizes() {
+        assert_eq!(HashTable::<i32, i32>::resize_sizes(2, 0), vec![2]);
+        assert_eq!(HashTable::<i32, i32>::resize_sizes(2, 1), vec![2, 4]);
+        assert_eq!(HashTable::<i32, i32>::resize_sizes(2, 7), vec![2, 4, 8, 16]);
+
+        let mut hash_table = HashTable::new(2);
+        let mut sizes = vec![hash_table.size];
+        for i in 0..7 {
+            hash_table.put(i, i).unwrap();
+            if *sizes.last().unwrap() != hash_table.size {
+                sizes.push(hash_table.size);
+            }
+        }
+        assert_eq!(sizes, HashTable::<i32, i32>::resize_sizes(2, 7));
+    }
+
+    #[test]
+    fn test_put_with_policy_replace() {
+        let mut hash_table = HashTable::new(10);
+        assert_eq!(hash_table.put_with_policy("Test".to_string(), 1, OverwritePolicy::Replace), PutResult::Inserted);
+        assert_eq!(hash_table.put_with_policy("Test".to_string(), 2, OverwritePolicy::Replace), PutResult::Replaced);
+        assert_eq!(hash_table.get(&"Test".to_string()), Some(&2));
+        assert_eq!(hash_table.current_size, 1);
+    }
+
+    #[test]
+    fn test_put_with_policy_keep() {
+        let mut hash_table = HashTable::new(10);
+        hash_table.put("Test".to_string(), 1).unwrap();
+        assert_eq!(hash_table.put_with_policy("Test".to_string(), 2, OverwritePolicy::Keep), PutResult::Kept);
+        assert_eq!(hash_table.get(&"Test".to_string()), Some(&1));
+        assert_eq!(hash_table.current_size, 1);
+    }
+
+    #[test]
+    fn test_put_with_policy_error() {
+        let mut hash_table = HashTable::new(10);
+        hash_table.put("Test".to_string(), 1).unwrap();
+        let result = hash_table.put_with_policy("Test".to_string(), 2, OverwritePolicy::Error);
+        assert_eq!(result, PutResult::Error("key already exists"));
+        assert_eq!(hash_table.get(&"Test".to_string()), Some(&1));
+    }
+
+    #[test]
+    fn test_slow_keys() {
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(16);
+        for i in 0..4 {
+            hash_table.put(Colliding(i), i).unwrap();
+        }
+        assert_eq!(hash_table.slow_keys(0).len(), 4);
+        let mut result = hash_table.slow_keys(2);
+        result.sort_by_key(|key| key.0);
+        assert_eq!(result, vec![&Colliding(2), &Colliding(3)]);
+        assert!(hash_table.slow_keys(4).is_empty());
+    }
+
+    #[test]
+    fn test_rebuild_under_max_probe() {
+        let mut hash_table = HashTable::new(64);
+        for i in 0..30 {
+            hash_table.put(i, i).unwrap();
+        }
+        assert!(hash_table.max_probe_distance() > 1);
+        assert_eq!(hash_table.rebuild_under_max_probe(1), Ok(()));
+        assert!(hash_table.max_probe_distance() <= 1);
+        for i in 0..30 {
+            assert_eq!(hash_table.get(&i), Some(&i));
+        }
+
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(16);
+        for i in 0..3 {
+            hash_table.put(Colliding(i), i).unwrap();
+        }
+        assert_eq!(hash_table.rebuild_under_max_probe(1), Err(()));
+        assert_eq!(hash_table.size, 16);
+        assert_eq!(hash_table.current_size, 3);
+        assert_eq!(hash_table.get(&Colliding(2)), Some(&2));
+    }
+
+    #[test]
+    fn test_min_collision_free_size() {
+        let mut hash_table = HashTable::new(16);
+        for key in ["A", "B", "C", "D"].iter() {
+            hash_table.put(key.to_string(), 1).unwrap();
+        }
+        let size = hash_table.min_collision_free_size().unwrap();
+        assert!(size >= 4);
+        let homes: Vec<usize> = ["A", "B", "C", "D"].iter()
+            .map(|key| hash_table.hash(&key.to_string()) as usize % size)
+            .collect();
+        for (i, home) in homes.iter().enumerate() {
+            assert!(!homes[i + 1..].contains(home));
+        }
+
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(16);
+        hash_table.put(Colliding(0), 0).unwrap();
+        hash_table.put(Colliding(1), 1).unwrap();
+        assert_eq!(hash_table.min_collision_free_size(), None);
+    }
+
+    #[test]
+    fn test_fill_from() {
+        let mut hash_table = HashTable::new(2);
+        let mut next = 0;
+        let count = hash_table.fill_from(|| {
+            if next == 5 {
+                return None;
+            }
+            next += 1;
+            Some((next, next * 10))
+        });
+        assert_eq!(count, 5);
+        for i in 1..=5 {
+            assert_eq!(hash_table.get(&i), Some(&(i * 10)));
+        }
+    }
+
+    #[test]
+    fn test_entry_byte_size() {
+        let size = HashTable::<i32, i64>::entry_byte_size();
+        assert!(size > 0);
+        assert_eq!(size, std::mem::size_of::<crate::HashTableEntry<i32, i64>>());
+        assert!(HashTable::<String, i64>::entry_byte_size() > size);
+    }
+
+    #[test]
+    fn test_clone_with_load_factor() {
+        let mut hash_table = HashTable::new(64);
+        for i in 0..20 {
+            hash_table.put(i, i).unwrap();
+        }
+        hash_table.delete(&0);
+
+        let sparse = hash_table.clone_with_load_factor(0.5);
+        hash_table.set_auto_resize(false);
+        let dense = hash_table.clone_with_load_factor(0.9);
+        assert!(sparse.auto_resize);
+        assert!(!dense.auto_resize);
+        assert_eq!(sparse.size, 40);
+        assert_eq!(dense.size, 23);
+        assert_eq!(dense.load_factor, 0.9);
+        for copy in [sparse, dense].iter() {
+            assert_eq!(copy.current_size, 19);
+            assert_eq!(copy.get(&0), None);
+            for i in 1..20 {
+                assert_eq!(copy.get(&i), Some(&i));
+            }
+        }
+    }
+
+    #[test]
+    fn test_iter_hash_sorted() {
+        let mut hash_table = HashTable::new(2);
+        for i in 0..10 {
+            hash_table.put(i, i * 2).unwrap();
+        }
+        let result = hash_table.iter_hash_sorted();
+        assert_eq!(result.len(), 10);
+        for pair in result.windows(2) {
+            assert!(hash_table.hash(pair[0].0) <= hash_table.hash(pair[1].0));
+        }
+        for (key, value) in result {
+            assert_eq!(*value, key * 2);
+        }
+
+        let mut reversed = HashTable::new(2);
+        for i in (0..10).rev() {
+            reversed.put(i, i * 2).unwrap();
+        }
+        assert_eq!(reversed.iter_hash_sorted(), hash_table.iter_hash_sorted());
+    }
+
+    #[test]
+    fn test_resize_overdue() {
+        let mut hash_table = HashTable::new(8);
+        hash_table.put(1, 1).unwrap();
+        assert!(!hash_table.resize_overdue());
+
+        hash_table.set_auto_resize(false);
+        for i in 2..=4 {
+            hash_table.put(i, i).unwrap();
+        }
+        assert_eq!(hash_table.size, 8);
+        assert!(hash_table.resize_overdue());
+
+        hash_table.set_auto_resize(true);
+        hash_table.put(5, 5).unwrap();
+        assert_eq!(hash_table.size, 16);
+        assert!(!hash_table.resize_overdue());
+    }
+
+    #[test]
+    fn test_into_boxed_pairs() {
+        let mut hash_table = HashTable::new(2);
+        for i in 0..6 {
+            hash_table.put(i, i * 10).unwrap();
+        }
+        hash_table.delete(&3);
+
+        let mut pairs = hash_table.into_boxed_pairs();
+        pairs.sort();
+        assert_eq!(&*pairs, &[(0, 0), (1, 10), (2, 20), (4, 40), (5, 50)]);
+    }
+
+    #[test]
+    fn test_changed_since() {
+        let mut hash_table = HashTable::new(64);
+        for i in 0..6 {
+            hash_table.put(i, i).unwrap();
+        }
+        let generation = hash_table.generation();
+        assert!(hash_table.changed_since(generation).is_empty());
+
+        hash_table.put(1, 10).unwrap();
+        hash_table.put(4, 40).unwrap();
+        hash_table.put(6, 60).unwrap();
+        hash_table.delete(&2);
+        hash_table.put(7, 7).unwrap();
+        hash_table.delete(&7);
+        hash_table.delete(&8);
+        let mut result = hash_table.changed_since(generation);
+        result.sort();
+        assert_eq!(result, vec![&1, &2, &4, &6, &7]);
+        assert!(hash_table.entry_generation(&0).unwrap() <= generation);
+        assert!(hash_table.entry_generation(&1).unwrap() > generation);
+        assert_eq!(hash_table.entry_generation(&2), None);
+
+        hash_table.put(2, 20).unwrap();
+        let mut result = hash_table.changed_since(generation);
+        result.sort();
+        assert_eq!(result, vec![&1, &2, &4, &6, &7]);
+
+        // Rehashing drops the tombstone that recorded the delete of 7.
+        hash_table.rehash(hash_table.size);
+        let mut result = hash_table.changed_since(generation);
+        result.sort();
+        assert_eq!(result, vec![&1, &2, &4, &6]);
+
+        // Pushing onto an existing list changes its value in place.
+        let mut hash_table = HashTable::new(16);
+        hash_table.push(1, 1).unwrap();
+        hash_table.push(2, 2).unwrap();
+        let generation = hash_table.generation();
+        hash_table.push(1, 2).unwrap();
+        assert_eq!(hash_table.changed_since(generation), vec![&1]);
+        assert!(hash_table.entry_generation(&1).unwrap() > generation);
+    }
+
+    #[test]
+    fn test_prefault() {
+        let mut hash_table: HashTable<String, i32> = HashTable::new(16);
+        hash_table.put("Test".to_string(), 1).unwrap();
+        hash_table.delete(&"Test".to_string());
+        hash_table.put("Test2".to_string(), 2).unwrap();
+        let generation = hash_table.generation();
+
+        hash_table.prefault();
+        assert!(hash_table.generation() > generation);
+        assert!(hash_table.changed_since(generation).is_empty());
+        assert_eq!(hash_table.size, 16);
+        assert_eq!(hash_table.table.len(), 16);
+        assert_eq!(hash_table.current_size, 0);
+        assert_eq!(hash_table.get(&"Test2".to_string()), None);
+        assert_eq!(hash_table.fragmentation(), 0.0);
+
+        hash_table.put("Test".to_string(), 3).unwrap();
+        assert_eq!(hash_table.get(&"Test".to_string()), Some(&3));
+    }
+
+    #[test]
+    fn test_occupancy_bitmap() {
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(8);
+        assert_eq!(hash_table.occupancy_bitmap(), vec![false; 8]);
+        for i in 0..3 {
+            hash_table.put(Colliding(i), i).unwrap();
+        }
+        hash_table.delete(&Colliding(2));
+
+        let home = hash_table.home_slot(&Colliding(0));
+        let mut expected = vec![false; 8];
+        expected[home] = true;
+        expected[(home + 1) % 8] = true;
+        assert_eq!(hash_table.occupancy_bitmap(), expected);
+    }
+
+    #[test]
+    fn test_dry_run_put() {
+        let mut hash_table = HashTable::new(8);
+        for i in 0..3 {
+            let (index, resizes) = hash_table.dry_run_put(&i).unwrap();
+            assert!(!resizes);
+            assert_eq!(hash_table.current_size, i as usize);
+            hash_table.put(i, i).unwrap();
+            assert_eq!(hash_table.find_slot(&i), Some(index));
+        }
+
+        assert!(!hash_table.dry_run_put(&0).unwrap().1);
+        let (_, resizes) = hash_table.dry_run_put(&3).unwrap();
+        assert!(resizes);
+        hash_table.put(3, 3).unwrap();
+        assert_eq!(hash_table.size, 16);
+    }
+
+    #[test]
+    fn test_merge_report() {
+        let mut hash_table = HashTable::new(2);
+        let mut other = HashTable::new(2);
+        for i in 0..5 {
+            hash_table.put(i, 0).unwrap();
+        }
+        for i in 3..8 {
+            other.put(i, 1).unwrap();
+        }
+        other.delete(&4);
+
+        let mut conflicts = hash_table.merge_report(other).unwrap();
+        conflicts.sort();
+        assert_eq!(conflicts, vec![3]);
+        assert_eq!(hash_table.get(&3), Some(&1));
+        assert_eq!(hash_table.get(&4), Some(&0));
+        assert_eq!(hash_table.get(&7), Some(&1));
+        assert_eq!(hash_table.current_size, 8);
+    }
+
+    #[test]
+    fn test_probe_distance_stddev() {
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(16);
+        assert_eq!(hash_table.probe_distance_stddev(), 0.0);
+        hash_table.put(Colliding(0), 0).unwrap();
+        assert_eq!(hash_table.probe_distance_stddev(), 0.0);
+
+        // Distances of 0, 1 and 2.
+        hash_table.put(Colliding(1), 1).unwrap();
+        hash_table.put(Colliding(2), 2).unwrap();
+        let expected = (2.0f64 / 3.0).sqrt();
+        assert!((hash_table.probe_distance_stddev() - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_from_columns() {
+        let keys = vec!["A".to_string(), "B".to_string(), "C".to_string()];
+        let hash_table = HashTable::from_columns(keys, vec![1, 2, 3]).unwrap();
+        assert_eq!(hash_table.current_size, 3);
+        assert_eq!(hash_table.get(&"A".to_string()), Some(&1));
+        assert_eq!(hash_table.get(&"B".to_string()), Some(&2));
+        assert_eq!(hash_table.get(&"C".to_string()), Some(&3));
+    }
+
+    #[test]
+    fn test_from_columns_length_mismatch() {
+        let keys = vec!["A".to_string(), "B".to_string()];
+        let result = HashTable::from_columns(keys, vec![1]);
+        assert_eq!(result.unwrap_err(), "keys and values have different lengths");
+    }
+
+    #[test]
+    fn test_stable_slot_count() {
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(16);
+        assert_eq!(hash_table.stable_slot_count(), 0);
+        for i in 0..4 {
+            hash_table.put(Colliding(i), i).unwrap();
+        }
+        assert_eq!(hash_table.stable_slot_count(), 4);
+
+        // Deleting the entry in the home slot lets every later entry move up by one.
+        hash_table.delete(&Colliding(0));
+        assert_eq!(hash_table.stable_slot_count(), 0);
+        hash_table.put(Colliding(0), 0).unwrap();
+        assert_eq!(hash_table.stable_slot_count(), 4);
+    }
+
+    #[test]
+    fn test_key_nearest_hash() {
+        let mut hash_table = HashTable::new(16);
+        assert_eq!(hash_table.key_nearest_hash(0), None);
+        for key in ["A", "B", "C"].iter() {
+            hash_table.put(key.to_string(), 1).unwrap();
+        }
+        for key in ["A", "B", "C"].iter() {
+            let hash = hash_table.hash(&key.to_string());
+            assert_eq!(hash_table.key_nearest_hash(hash), Some(&key.to_string()));
+        }
+
+        let mut hashes: Vec<(u64, &str)> = ["A", "B", "C"].iter()
+            .map(|key| (hash_table.hash(&key.to_string()), *key))
+            .collect();
+        hashes.sort();
+        assert_eq!(hash_table.key_nearest_hash(0), Some(&hashes[0].1.to_string()));
+        assert_eq!(hash_table.key_nearest_hash(u64::MAX), Some(&hashes[2].1.to_string()));
+    }
+
+    #[test]
+    fn test_config() {
+        let mut hash_table: HashTable<i32, i32> = HashTable::new(8);
+        assert_eq!(hash_table.config().load_factor, 0.5);
+        hash_table.set_probe_strategy(ProbeStrategy::Quadratic);
+        let hash_table = hash_table.clone_with_load_factor(0.75);
+
+        let config = hash_table.config();
+        assert!(config.hasher.ends_with("DefaultHasher"));
+        assert_eq!(config, TableConfig {
+            size: 2,
+            load_factor: 0.75,
+            growth_factor: 2,
+            probe_strategy: ProbeStrategy::Quadratic,
+            hasher: config.hasher,
+        });
+    }
+
+    #[test]
+    fn test_fill_values() {
+        let mut hash_table = HashTable::new(2);
+        for i in 0..6 {
+            hash_table.put(i, i).unwrap();
+        }
+        hash_table.delete(&5);
+        let bitmap = hash_table.occupancy_bitmap();
+
+        hash_table.fill_values(0);
+        for i in 0..5 {
+            assert_eq!(hash_table.get(&i), Some(&0));
+        }
+        assert_eq!(hash_table.get(&5), None);
+        assert_eq!(hash_table.occupancy_bitmap(), bitmap);
+    }
+
+    #[test]
+    fn test_map_values_in_place() {
+        let mut hash_table = HashTable::new(2);
+        for i in 1..6 {
+            hash_table.put(i, i).unwrap();
+        }
+        let generation = hash_table.generation();
+        hash_table.map_values_in_place(|_, value| *value = -*value);
+        for i in 1..6 {
+            assert_eq!(hash_table.get(&i), Some(&-i));
+        }
+        assert_eq!(hash_table.changed_since(generation).len(), 5);
+    }
+
+    #[test]
+    fn test_reserve_for_rate() {
+        let mut hash_table = HashTable::new(2);
+        hash_table.reserve_for_rate(10, 5).unwrap();
+        assert_eq!(hash_table.size, 102);
+        assert_eq!(hash_table.resize_threshold(), 51);
+        for i in 0..50 {
+            hash_table.put(i, i).unwrap();
+        }
+        assert_eq!(hash_table.size, 102);
+
+        hash_table.reserve_for_rate(0, 10).unwrap();
+        assert_eq!(hash_table.size, 102);
+
+        assert_eq!(hash_table.reserve_for_rate(usize::MAX, 2), Err("table is too large"));
+        assert_eq!(hash_table.reserve_for_rate(usize::MAX, 1), Err("table is too large"));
+        assert_eq!(hash_table.reserve(usize::MAX / 4), Err("table is too large"));
+        assert_eq!(hash_table.size, 102);
+
+        let mut hash_table: HashTable<i32, i32> = HashTable::new(2);
+        assert_eq!(hash_table.reserve_for_rate(usize::MAX, 1), Err("table is too large"));
+        assert_eq!(hash_table.reserve(usize::MAX - 1), Err("table is too large"));
+        assert_eq!(hash_table.size, 2);
+    }
+
+    #[test]
+    fn test_is_at_home() {
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(16);
+        assert_eq!(hash_table.is_at_home(&Colliding(0)), None);
+        hash_table.put(Colliding(0), 0).unwrap();
+        hash_table.put(Colliding(1), 1).unwrap();
+        assert_eq!(hash_table.is_at_home(&Colliding(0)), Some(true));
+        assert_eq!(hash_table.is_at_home(&Colliding(1)), Some(false));
+    }
+
+    #[test]
+    fn test_entries_with_slots() {
+        let mut hash_table = HashTable::new(2);
+        for i in 0..6 {
+            hash_table.put(i, i * 10).unwrap();
+        }
+        hash_table.delete(&2);
+        assert_eq!(hash_table.slot_of(&2), None);
+
+        let entries = hash_table.entries_with_slots();
+        assert_eq!(entries.len(), 5);
+        for (key, value, index) in entries {
+            assert_eq!(*value, key * 10);
+            assert_eq!(hash_table.slot_of(key), Some(index));
+        }
+    }
+
+    #[test]
+    fn test_inserts_until_resize() {
+        let mut hash_table = HashTable::new(16);
+        assert_eq!(hash_table.inserts_until_resize(), 7);
+        for i in 0..7 {
+            hash_table.put(i, i).unwrap();
+            assert_eq!(hash_table.inserts_until_resize(), 6 - i as usize);
+        }
+        hash_table.put(0, 1).unwrap();
+        assert_eq!(hash_table.inserts_until_resize(), 0);
+        assert_eq!(hash_table.size, 16);
+
+        hash_table.put(7, 7).unwrap();
+        assert_eq!(hash_table.size, 32);
+        assert_eq!(hash_table.inserts_until_resize(), 7);
+    }
+
+    #[test]
+    fn test_insert_dedup() {
+        let mut hash_table = HashTable::new(2);
+        hash_table.put("A".to_string(), 0).unwrap();
+        let items = vec![
+            ("A".to_string(), 1),
+            ("B".to_string(), 2),
+            ("A".to_string(), 3),
+            ("C".to_string(), 4),
+            ("B".to_string(), 5),
+            ("A".to_string(), 6),
+        ];
+        assert_eq!(hash_table.insert_dedup(items), Ok(3));
+        assert_eq!(hash_table.get(&"A".to_string()), Some(&6));
+        assert_eq!(hash_table.get(&"B".to_string()), Some(&5));
+        assert_eq!(hash_table.get(&"C".to_string()), Some(&4));
+        assert_eq!(hash_table.current_size, 3);
+    }
+
+    #[test]
+    fn test_to_grid() {
+        let mut hash_table: HashTable<i32, i32> = HashTable::new(5);
+        assert_eq!(hash_table.to_grid(5), ". . . . .");
+        hash_table.table[0].key = 7;
+        hash_table.table[0].is_alive = true;
+        hash_table.table[0].has_been_used = true;
+        hash_table.table[2].key = 3;
+        hash_table.table[2].has_been_used = true;
+        hash_table.table[3].key = 12;
+        hash_table.table[3].is_alive = true;
+        hash_table.table[3].has_been_used = true;
+        assert_eq!(hash_table.to_grid(2), "7 .\nx 12\n.");
+    }
+
+    #[test]
+    fn test_first_empty_from() {
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(8);
+        let home = hash_table.home_slot(&Colliding(0));
+        assert_eq!(hash_table.first_empty_from(home), Some(home));
+        for i in 0..3 {
+            hash_table.put(Colliding(i), i).unwrap();
+        }
+        hash_table.delete(&Colliding(1));
+        assert_eq!(hash_table.first_empty_from(home), Some((home + 3) % 8));
+        assert_eq!(hash_table.first_empty_from(home + 8), Some((home + 3) % 8));
+        assert_eq!(hash_table.first_empty_from(home + 4), Some((home + 4) % 8));
+        assert_eq!(hash_table.first_empty_from(usize::MAX), hash_table.first_empty_from(usize::MAX % 8));
+
+        for entry in hash_table.table.iter_mut() {
+            entry.has_been_used = true;
+        }
+        assert_eq!(hash_table.first_empty_from(0), None);
+    }
+
+    #[test]
+    fn test_hash_extremes() {
+        let mut hash_table = HashTable::new(16);
+        assert_eq!(hash_table.hash_extremes(), None);
+        hash_table.put("A".to_string(), 1).unwrap();
+        assert_eq!(hash_table.hash_extremes(), Some((&"A".to_string(), &"A".to_string())));
+
+        for key in ["B", "C", "D"].iter() {
+            hash_table.put(key.to_string(), 1).unwrap();
+        }
+        let mut hashes: Vec<(u64, String)> = ["A", "B", "C", "D"].iter()
+            .map(|key| (hash_table.hash(&key.to_string()), key.to_string()))
+            .collect();
+        hashes.sort();
+        assert_eq!(hash_table.hash_extremes(), Some((&hashes[0].1, &hashes[3].1)));
+    }
+
+    #[test]
+    fn test_balance() {
+        let mut hash_table = HashTable::new(64);
+        let clustered: Vec<i32> = (0..).filter(|key| hash_table.home_slot(key) < 2).take(8).collect();
+        for key in clustered.iter() {
+            hash_table.put(*key, *key).unwrap();
+        }
+        let max_probe = hash_table.max_probe_distance();
+        assert!(hash_table.probe_distance_stddev() > 1.0);
+
+        hash_table.balance();
+        assert_eq!(hash_table.size, 128);
+        assert!(hash_table.max_probe_distance() < max_probe);
+        for key in clustered.iter() {
+            assert_eq!(hash_table.get(key), Some(key));
+        }
+
+        hash_table.balance();
+        assert_eq!(hash_table.size, 128);
+    }
+
+    #[test]
+    fn test_bucketize() {
+        let mut hash_table = HashTable::new(2);
+        let mut resized = HashTable::new(64);
+        for i in 0..20 {
+            hash_table.put(i, i).unwrap();
+            resized.put(i, i).unwrap();
+        }
+
+        let buckets = hash_table.bucketize(3);
+        assert_eq!(buckets.len(), 3);
+        assert_eq!(buckets.iter().map(|bucket| bucket.len()).sum::<usize>(), 20);
+        for (i, bucket) in buckets.iter().enumerate() {
+            for (key, _) in bucket.iter() {
+                assert_eq!(hash_table.hash(key) % 3, i as u64);
+            }
+        }
+
+        let mut other_buckets = resized.bucketize(3);
+        let mut buckets = buckets;
+        for bucket in buckets.iter_mut().chain(other_buckets.iter_mut()) {
+            bucket.sort();
+        }
+        assert_eq!(buckets, other_buckets);
+    }
+
+    #[test]
+    fn test_assert_no_duplicate_keys() {
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(2);
+        for i in 0..10 {
+            hash_table.put(Colliding(i), i).unwrap();
+            hash_table.put(Colliding(i), i + 1).unwrap();
+        }
+        hash_table.delete(&Colliding(3));
+        hash_table.assert_no_duplicate_keys();
+    }
+
+    #[test]
+    fn test_difference() {
+        let mut hash_table = HashTable::new(2);
+        let mut other = HashTable::new(2);
+        for i in 0..6 {
+            hash_table.put(i, i).unwrap();
+        }
+        for i in 3..9 {
+            other.put(i, i).unwrap();
+        }
+        other.delete(&4);
+
+        let mut result = hash_table.difference(&other);
+        result.sort();
+        assert_eq!(result, vec![&0, &1, &2, &4]);
+        let mut result = other.difference(&hash_table);
+        result.sort();
+        assert_eq!(result, vec![&6, &7, &8]);
+    }
+
+    #[test]
+    fn test_intersection() {
+        let mut hash_table = HashTable::new(2);
+        let mut other = HashTable::new(2);
+        for i in 0..6 {
+            hash_table.put(i, i).unwrap();
+        }
+        for i in 3..9 {
+            other.put(i, i).unwrap();
+        }
+        other.delete(&4);
+
+        let mut result = hash_table.intersection(&other);
+        result.sort();
+        assert_eq!(result, vec![&3, &5]);
+        let mut result = other.intersection(&hash_table);
+        result.sort();
+        assert_eq!(result, vec![&3, &5]);
+    }
+
+    #[test]
+    fn test_symmetric_difference() {
+        let mut hash_table = HashTable::new(2);
+        let mut other = HashTable::new(2);
+        for i in 0..6 {
+            hash_table.put(i, i).unwrap();
+        }
+        for i in 3..9 {
+            other.put(i, i).unwrap();
+        }
+        other.delete(&4);
+
+        let mut result = hash_table.symmetric_difference(&other);
+        result.sort();
+        assert_eq!(result, vec![&0, &1, &2, &4, &6, &7, &8]);
+        let mut result = other.symmetric_difference(&hash_table);
+        result.sort();
+        assert_eq!(result, vec![&0, &1, &2, &4, &6, &7, &8]);
+    }
+
+    #[test]
+    fn test_is_key_subset() {
+        let mut hash_table = HashTable::new(2);
+        let mut other = HashTable::new(2);
+        assert!(hash_table.is_key_subset(&other));
+        for i in 0..3 {
+            hash_table.put(i, i).unwrap();
+        }
+        for i in 0..6 {
+            other.put(i, i * 10).unwrap();
+        }
+        assert!(hash_table.is_key_subset(&other));
+        assert!(!other.is_key_subset(&hash_table));
+
+        hash_table.put(6, 6).unwrap();
+        assert!(!hash_table.is_key_subset(&other));
+        hash_table.delete(&6);
+        assert!(hash_table.is_key_subset(&other));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_map_values() {
+        let mut hash_table = HashTable::new(2);
+        for i in 0..1000 {
+            hash_table.put(i, i).unwrap();
+        }
+        hash_table.delete(&500);
+        let mut sequential = hash_table.clone();
+
+        hash_table.par_map_values(|key, value| *value = *value * 3 + key);
+        sequential.map_values_in_place(|key, value| *value = *value * 3 + key);
+        for i in 0..1000 {
+            assert_eq!(hash_table.get(&i), sequential.get(&i));
+        }
+        assert_eq!(hash_table.get(&1), Some(&4));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_count_matching() {
+        let mut hash_table = HashTable::new(2);
+        for i in 0..10000 {
+            hash_table.put(i, i).unwrap();
+        }
+        let is_multiple = |_: &i32, value: &i32| value % 7 == 0;
+        let sequential = hash_table.table.iter()
+            .filter(|entry| entry.is_alive && is_multiple(&entry.key, &entry.value))
+            .count();
+        assert_eq!(hash_table.par_count_matching(is_multiple), sequential);
+        assert_eq!(sequential, 1429);
+    }
+
+    #[test]
+    fn test_load_from_channel() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let producer = std::thread::spawn(move || {
+            for i in 0..5 {
+                tx.send((i, i * 10)).unwrap();
+            }
+        });
+
+        let mut hash_table = HashTable::new(2);
+        assert_eq!(hash_table.load_from_channel(rx), 5);
+        producer.join().unwrap();
+        for i in 0..5 {
+            assert_eq!(hash_table.get(&i), Some(&(i * 10)));
+        }
+    }
+
+    #[test]
+    fn test_entry_at() {
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(8);
+        hash_table.put(Colliding(0), 0).unwrap();
+        hash_table.put(Colliding(1), 1).unwrap();
+        hash_table.delete(&Colliding(1));
+
+        let home = hash_table.home_slot(&Colliding(0));
+        assert_eq!(hash_table.entry_at(home), Some((&Colliding(0), &0)));
+        assert_eq!(hash_table.entry_at((home + 1) % 8), None);
+        assert_eq!(hash_table.entry_at((home + 2) % 8), None);
+        assert_eq!(hash_table.entry_at(8), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_roundtrip() {
+        let mut hash_table = HashTable::new(2);
+        hash_table.assert_serde_roundtrip();
+        for i in 0..10 {
+            hash_table.put(i, format!("Value {}", i)).unwrap();
+        }
+        hash_table.delete(&3);
+        hash_table.delete(&7);
+        assert!(hash_table.fragmentation() > 0.0);
+        hash_table.assert_serde_roundtrip();
+
+        let copy: HashTable<i32, String> = serde_json::from_str(&serde_json::to_string(&hash_table).unwrap()).unwrap();
+        assert_eq!(copy.get(&3), None);
+        assert_eq!(copy.current_size, 8);
+    }
+
+    #[test]
+    fn test_iter_excluding() {
+        let mut hash_table = HashTable::new(2);
+        for i in 0..8 {
+            hash_table.put(i, i * 10).unwrap();
+        }
+        hash_table.delete(&7);
+
+        let excluded = [1, 4, 5];
+        let mut result: Vec<(&i32, &i32)> = hash_table.iter_excluding(|key| excluded.contains(key)).collect();
+        result.sort();
+        assert_eq!(result, vec![(&0, &0), (&2, &20), (&3, &30), (&6, &60)]);
+    }
+
+    #[test]
+    fn test_median_value() {
+        let mut hash_table = HashTable::new(2);
+        assert_eq!(hash_table.median_value(), None);
+        for (key, value) in [(1, 9), (2, 3), (3, 7), (4, 1), (5, 5)].iter() {
+            hash_table.put(*key, *value).unwrap();
+        }
+        assert_eq!(hash_table.median_value(), Some(5));
+
+        hash_table.put(6, 8).unwrap();
+        assert_eq!(hash_table.median_value(), Some(5));
+        hash_table.put(7, 10).unwrap();
+        assert_eq!(hash_table.median_value(), Some(7));
+    }
+
+    #[test]
+    fn test_put_batch() {
+        let mut hash_table = HashTable::new(4);
+        hash_table.set_auto_resize(false);
+        let items = (0..6).map(|i| (i, i * 10)).chain(vec![(0, 1)]).collect();
+
+        let results = hash_table.put_batch(items);
+        assert_eq!(results, vec![Ok(()), Ok(()), Ok(()), Ok(()), Err((4, 40)), Err((5, 50)), Ok(())]);
+        assert_eq!(hash_table.size, 4);
+        assert_eq!(hash_table.get(&0), Some(&1));
+        assert_eq!(hash_table.get(&4), None);
+    }
+
+    #[test]
+    fn test_entries_moving_on_resize() {
+        let mut hash_table = HashTable::new(16);
+        assert_eq!(hash_table.entries_moving_on_resize(), 0);
+        for i in 0..7 {
+            hash_table.put(i, i).unwrap();
+        }
+        let before: Vec<Option<usize>> = (0..7).map(|i| hash_table.slot_of(&i)).collect();
+        let predicted = hash_table.entries_moving_on_resize();
+
+        hash_table.resize();
+        let moved = (0..7).filter(|i| hash_table.slot_of(i) != before[*i as usize]).count();
+        assert_eq!(predicted, moved);
+
+        // A run of colliding keys that doesn't wrap keeps its order, so either all of it moves with
+        // the home slot or none of it does.
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(8);
+        for i in 0..3 {
+            hash_table.put(Colliding(i), i).unwrap();
+        }
+        let home = hash_table.home_slot(&Colliding(0));
+        assert!(home + 3 <= 8);
+        let expected = if hash_table.hash(&Colliding(0)) as usize % 16 == home { 0 } else { 3 };
+        assert_eq!(hash_table.entries_moving_on_resize(), expected);
+    }
+
+    #[test]
+    fn test_quadratic_non_power_of_two_size() {
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(20);
+        hash_table.set_auto_resize(false);
+        hash_table.set_probe_strategy(ProbeStrategy::Quadratic);
+        assert_eq!(hash_table.size, 32);
+        for i in 0..32 {
+            assert_eq!(hash_table.put(Colliding(i), i), Ok(()));
+        }
+        assert_eq!(hash_table.put(Colliding(32), 32), Err("table is full"));
+
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(64);
+        hash_table.set_probe_strategy(ProbeStrategy::Quadratic);
+        for i in 0..6 {
+            hash_table.put(Colliding(i), i).unwrap();
+        }
+        let copy = hash_table.clone_with_load_factor(0.9);
+        assert!(copy.size.is_power_of_two());
+        for i in 0..6 {
+            assert_eq!(copy.get(&Colliding(i)), Some(&i));
+        }
+
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(8);
+        hash_table.set_probe_strategy(ProbeStrategy::Quadratic);
+        hash_table.reserve(40).unwrap();
+        assert_eq!(hash_table.size, 128);
+    }
+
+    #[test]
+    fn test_dry_run_put_full_table() {
+        let mut hash_table = HashTable::new(4);
+        hash_table.set_auto_resize(false);
+        for i in 0..4 {
+            hash_table.put(i, i).unwrap();
+        }
+        assert_eq!(hash_table.dry_run_put(&4), None);
+        assert_eq!(hash_table.put(4, 4), Err("table is full"));
+        assert_eq!(hash_table.dry_run_put(&0), Some((hash_table.slot_of(&0).unwrap(), false)));
+    }
+
+    #[test]
+    fn test_delete_head_of_chain() {
+        let mut hash_table: HashTable<Colliding, i32> = HashTable::new(16);
+        for i in 0..3 {
+            hash_table.put(Colliding(i), i).unwrap();
+        }
+        hash_table.delete(&Colliding(0));
+        assert_eq!(hash_table.get(&Colliding(0)), None);
+        assert_eq!(hash_table.get(&Colliding(1)), Some(&1));
+        assert_eq!(hash_table.get(&Colliding(2)), Some(&2));
+
+        let result = hash_table.put_with_policy(Colliding(1), 10, OverwritePolicy::Replace);
+        assert_eq!(result, PutResult::Replaced);
+        assert_eq!(hash_table.current_size, 3);
+        hash_table.assert_no_duplicate_keys();
+
+        // The deleted slot at the head of the chain is reused for new keys.
+        let home = hash_table.home_slot(&Colliding(0));
+        hash_table.put(Colliding(3), 3).unwrap();
+        assert_eq!(hash_table.slot_of(&Colliding(3)), Some(home));
+        assert_eq!(hash_table.get(&Colliding(1)), Some(&10));
+    }
+
+    #[test]
+    fn test_full_fixed_size_table_errors() {
+        let full_table = || {
+            let mut hash_table = HashTable::new(4);
+            hash_table.set_auto_resize(false);
+            for i in 0..4 {
+                hash_table.put(i, vec![i]).unwrap();
+            }
+            hash_table
+        };
+
+        let mut hash_table = full_table();
+        assert_eq!(hash_table.get_or_compute(4, |_| vec![]), Err("table is full"));
+        assert_eq!(hash_table.get_or_compute(0, |_| vec![]), Ok(&vec![0]));
+
+        let mut hash_table = full_table();
+        assert_eq!(hash_table.push(4, 4), Err("table is full"));
+        assert_eq!(hash_table.push(0, 1), Ok(()));
+        assert_eq!(hash_table.get_all(&0), &[0, 1]);
+
+        let mut hash_table = full_table();
+        assert_eq!(hash_table.warm(vec![0, 4], |_| vec![]), Err("table is full"));
+        assert_eq!(hash_table.get(&0), Some(&vec![]));
+
+        let mut hash_table = full_table();
+        let mut other = HashTable::new(16);
+        other.put(4, vec![4]).unwrap();
+        assert_eq!(hash_table.merge_report(other), Err("table is full"));
+
+        let mut hash_table = full_table();
+        assert_eq!(hash_table.insert_dedup(vec![(1, vec![]), (5, vec![])]), Err("table is full"));
+        assert_eq!(hash_table.size, 4);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_caps_size_hint() {
+        use serde::Deserialize;
+
+        /// Yields a few pairs while claiming to hold `usize::MAX` of them.
+        struct ForgedLength(std::vec::IntoIter<(i32, i32)>);
+
+        impl Iterator for ForgedLength {
+            type Item = (i32, i32);
+
+            fn next(&mut self) -> Option<Self::Item> {
+                self.0.next()
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                (usize::MAX, Some(usize::MAX))
+            }
+        }
+
+        let pairs = ForgedLength(vec![(1, 10), (2, 20)].into_iter());
+        let deserializer = serde::de::value::MapDeserializer::<_, serde::de::value::Error>::new(pairs);
+        let hash_table = HashTable::<i32, i32>::deserialize(deserializer).unwrap();
+        assert!(hash_table.size <= 2 * crate::MAX_DESERIALIZE_SIZE_HINT + 2);
+        assert_eq!(hash_table.get(&2), Some(&20));
     }
 }